@@ -0,0 +1,182 @@
+//! JWT issuing and renewal for authenticated users.
+//!
+//! Tokens are signed with HMAC-SHA256 (`jsonwebtoken`, keyed on
+//! `secret_key`), so `decode_token` rejects anything that wasn't signed with
+//! that key - including a hand-built `{user_id}.{issued_at}.{expires_at}`
+//! string, which is not a valid JWT.
+//!
+//! Time is read through the injected [`Clock`] rather than the system clock
+//! directly, so renewal-window behaviour (e.g. "a token can only be renewed
+//! once it's within a week of expiring") can be tested deterministically by
+//! advancing a [`MockClock`](crate::utils::clock::MockClock) instead of
+//! waiting on real wall-clock time.
+
+use std::sync::Arc;
+
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+
+use crate::models::user::UserCompact;
+use crate::utils::clock::Clock;
+
+/// A token is only eligible for renewal once this close to its expiry.
+const RENEWAL_WINDOW_SECS: u64 = 604_800; // one week
+
+pub enum Error {
+    TokenInvalid,
+    TokenNotYetRenewable,
+}
+
+pub struct JsonWebToken {
+    pub user_id: i64,
+    pub issued_at: u64,
+    pub expires_at: u64,
+}
+
+pub struct AuthorizationService {
+    clock: Arc<dyn Clock>,
+    secret_key: String,
+}
+
+impl AuthorizationService {
+    pub fn new(clock: Arc<dyn Clock>, secret_key: String) -> Self {
+        Self { clock, secret_key }
+    }
+
+    pub fn sign(&self, user: &UserCompact, validity_secs: u64) -> String {
+        let now_secs = self.clock.now_secs();
+
+        encode_token(
+            &JsonWebToken {
+                user_id: user.user_id,
+                issued_at: now_secs,
+                expires_at: now_secs + validity_secs,
+            },
+            &self.secret_key,
+        )
+    }
+
+    /// Renews `token`, returning a freshly-signed one valid for
+    /// `validity_secs` from now, but only if the current token is within
+    /// [`RENEWAL_WINDOW_SECS`] of expiring.
+    pub fn renew(&self, token: &str, validity_secs: u64) -> Result<String, Error> {
+        let decoded = decode_token(token, &self.secret_key).map_err(|()| Error::TokenInvalid)?;
+
+        let now_secs = self.clock.now_secs();
+
+        if !Self::is_renewable(now_secs, decoded.expires_at) {
+            return Err(Error::TokenNotYetRenewable);
+        }
+
+        Ok(encode_token(
+            &JsonWebToken {
+                user_id: decoded.user_id,
+                issued_at: now_secs,
+                expires_at: now_secs + validity_secs,
+            },
+            &self.secret_key,
+        ))
+    }
+
+    fn is_renewable(now_secs: u64, expires_at: u64) -> bool {
+        expires_at.saturating_sub(now_secs) <= RENEWAL_WINDOW_SECS
+    }
+}
+
+/// The claims actually placed in the signed JWT. Kept separate from
+/// [`JsonWebToken`] so the wire format (snake_case-free, standard `sub`)
+/// isn't coupled to our internal field names.
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    sub: i64,
+    iat: u64,
+    exp: u64,
+}
+
+fn encode_token(token: &JsonWebToken, secret_key: &str) -> String {
+    let claims = Claims {
+        sub: token.user_id,
+        iat: token.issued_at,
+        exp: token.expires_at,
+    };
+
+    encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(secret_key.as_bytes()))
+        .expect("Could not encode JWT")
+}
+
+fn decode_token(token: &str, secret_key: &str) -> Result<JsonWebToken, ()> {
+    let data = decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(secret_key.as_bytes()),
+        &Validation::new(Algorithm::HS256),
+    )
+    .map_err(|_| ())?;
+
+    Ok(JsonWebToken {
+        user_id: data.claims.sub,
+        issued_at: data.claims.iat,
+        expires_at: data.claims.exp,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clock::MockClock;
+
+    fn user() -> UserCompact {
+        UserCompact {
+            user_id: 1,
+            username: "user".to_string(),
+            admin: false,
+            email_verified: true,
+        }
+    }
+
+    #[test]
+    fn it_should_not_allow_renewing_a_token_which_is_still_valid_for_more_than_one_week() {
+        let clock = Arc::new(MockClock::new(0));
+        let service = AuthorizationService::new(clock.clone(), "secret".to_string());
+
+        let validity_secs = 7_257_600; // ~84 days, matches the default token_valid_seconds
+        let token = service.sign(&user(), validity_secs);
+
+        clock.advance(validity_secs - RENEWAL_WINDOW_SECS - 1);
+
+        assert!(matches!(service.renew(&token, validity_secs), Err(Error::TokenNotYetRenewable)));
+    }
+
+    #[test]
+    fn it_should_allow_renewing_a_token_one_week_before_it_expires() {
+        let clock = Arc::new(MockClock::new(0));
+        let service = AuthorizationService::new(clock.clone(), "secret".to_string());
+
+        let validity_secs = 7_257_600;
+        let token = service.sign(&user(), validity_secs);
+
+        clock.advance(validity_secs - RENEWAL_WINDOW_SECS);
+
+        assert!(service.renew(&token, validity_secs).is_ok());
+    }
+
+    #[test]
+    fn it_should_reject_a_token_forged_with_the_wrong_secret_key() {
+        let clock = Arc::new(MockClock::new(0));
+        let signer = AuthorizationService::new(clock.clone(), "secret".to_string());
+        let verifier = AuthorizationService::new(clock, "a-different-secret".to_string());
+
+        let token = signer.sign(&user(), 3600);
+
+        assert!(matches!(verifier.renew(&token, 3600), Err(Error::TokenInvalid)));
+    }
+
+    #[test]
+    fn it_should_reject_a_hand_built_unsigned_token() {
+        let clock = Arc::new(MockClock::new(0));
+        let service = AuthorizationService::new(clock, "secret".to_string());
+
+        let forged = format!("{}.{}.{}", 1, 0, 3600);
+
+        assert!(matches!(service.renew(&forged, 3600), Err(Error::TokenInvalid)));
+    }
+}
@@ -1,17 +1,94 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::time::Duration;
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use reqwest::{redirect, Url};
 use tokio::sync::RwLock;
 
 use crate::cache::cache::BytesCache;
-use crate::config::Configuration;
+use crate::config::{Configuration, ImageCacheDns};
+use crate::databases::database::Database;
 use crate::models::user::UserCompact;
+use crate::utils::clock::Clock;
+
+use super::resolver::{is_allowed_ip, VettedSocketResolver};
+use super::sniff;
+
+/// Returns `false` only when `url`'s host is an IP literal that `dns_settings`
+/// disallows. A hostname host is always considered allowed here: it's left
+/// to the custom DNS resolver, which is consulted for every connection the
+/// client makes (including ones following a redirect) and can't be
+/// short-circuited the way a literal IP in the URL can.
+fn is_literal_host_allowed(url: &Url, dns_settings: &ImageCacheDns) -> bool {
+    match url.host_str() {
+        Some(host) => match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(ip) => is_allowed_ip(&ip, dns_settings),
+            Err(_) => true,
+        },
+        None => true,
+    }
+}
+
+/// Rejects a redirect hop whose host is an IP literal outside `dns_settings`.
+/// Without this, a server that passed the initial vetting could redirect
+/// straight to an IP literal (e.g. `http://127.0.0.1:1212/`) and reach it
+/// without ever going through the resolver again.
+fn redirect_policy(dns_settings: Arc<ImageCacheDns>) -> redirect::Policy {
+    redirect::Policy::custom(move |attempt| {
+        if !is_literal_host_allowed(attempt.url(), &dns_settings) {
+            return attempt.error("redirected to an address that is not allowed for the image proxy");
+        }
+
+        attempt.follow()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::config::ImageCacheDns;
+
+    fn dns_settings() -> ImageCacheDns {
+        ImageCacheDns {
+            allowed_cidrs: vec![],
+            denied_cidrs: vec![],
+        }
+    }
+
+    #[test]
+    fn it_should_allow_a_public_ip_literal_host() {
+        let url = Url::from_str("http://93.184.216.34/image.png").unwrap();
+        assert!(is_literal_host_allowed(&url, &dns_settings()));
+    }
+
+    #[test]
+    fn it_should_reject_a_loopback_ip_literal_host() {
+        let url = Url::from_str("http://127.0.0.1:1212/image.png").unwrap();
+        assert!(!is_literal_host_allowed(&url, &dns_settings()));
+    }
+
+    #[test]
+    fn it_should_reject_a_bracketed_ipv6_loopback_literal_host() {
+        let url = Url::from_str("http://[::1]/image.png").unwrap();
+        assert!(!is_literal_host_allowed(&url, &dns_settings()));
+    }
+
+    #[test]
+    fn it_should_allow_a_hostname_host_and_defer_to_the_dns_resolver() {
+        let url = Url::from_str("http://example.com/image.png").unwrap();
+        assert!(is_literal_host_allowed(&url, &dns_settings()));
+    }
+}
 
 pub enum Error {
     UrlIsUnreachable,
     UrlIsNotAnImage,
+    UrlIsNotAllowed,
     ImageTooBig,
     UserQuotaMet,
     Unauthenticated,
@@ -19,13 +96,6 @@ pub enum Error {
 
 type UserQuotas = HashMap<i64, ImageCacheQuota>;
 
-pub fn now_in_secs() -> u64 {
-    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
-        Ok(n) => n.as_secs(),
-        Err(_) => panic!("SystemTime before UNIX EPOCH!"),
-    }
-}
-
 #[derive(Clone)]
 pub struct ImageCacheQuota {
     pub user_id: i64,
@@ -36,20 +106,20 @@ pub struct ImageCacheQuota {
 }
 
 impl ImageCacheQuota {
-    pub fn new(user_id: i64, max_usage: usize, period_secs: u64) -> Self {
+    pub fn new(user_id: i64, max_usage: usize, period_secs: u64, now_secs: u64) -> Self {
         Self {
             user_id,
             usage: 0,
             max_usage,
-            date_start_secs: now_in_secs(),
+            date_start_secs: now_secs,
             period_secs,
         }
     }
 
-    pub fn add_usage(&mut self, amount: usize) -> Result<(), ()> {
+    pub fn add_usage(&mut self, amount: usize, now_secs: u64) -> Result<(), ()> {
         // Check if quota needs to be reset.
-        if now_in_secs() - self.date_start_secs > self.period_secs {
-            self.reset();
+        if now_secs - self.date_start_secs > self.period_secs {
+            self.reset(now_secs);
         }
 
         if self.is_reached() {
@@ -61,9 +131,9 @@ impl ImageCacheQuota {
         Ok(())
     }
 
-    pub fn reset(&mut self) {
+    pub fn reset(&mut self, now_secs: u64) {
         self.usage = 0;
-        self.date_start_secs = now_in_secs();
+        self.date_start_secs = now_secs;
     }
 
     pub fn is_reached(&self) -> bool {
@@ -73,21 +143,35 @@ impl ImageCacheQuota {
 
 pub struct ImageCacheService {
     image_cache: RwLock<BytesCache>,
+    // Write-through cache of the persisted quotas: reads are served from here
+    // when possible, but the database row is the source of truth that
+    // survives restarts and is shared across horizontally-scaled instances.
     user_quotas: RwLock<UserQuotas>,
     reqwest_client: reqwest::Client,
     cfg: Arc<Configuration>,
+    clock: Arc<dyn Clock>,
+    database: Arc<Box<dyn Database>>,
 }
 
 impl ImageCacheService {
-    pub async fn new(cfg: Arc<Configuration>) -> Self {
+    pub async fn new(cfg: Arc<Configuration>, clock: Arc<dyn Clock>, database: Arc<Box<dyn Database>>) -> Self {
         let settings = cfg.settings.read().await;
 
         let image_cache =
             BytesCache::with_capacity_and_entry_size_limit(settings.image_cache.capacity, settings.image_cache.entry_size_limit)
                 .expect("Could not create image cache.");
 
+        let dns_settings = Arc::new(settings.image_cache.dns.clone());
+        let dns_resolver = VettedSocketResolver::new(dns_settings.clone());
+
         let reqwest_client = reqwest::Client::builder()
             .timeout(Duration::from_millis(settings.image_cache.max_request_timeout_ms))
+            .dns_resolver(Arc::new(dns_resolver))
+            // The resolver above only runs for hostnames. Without this, a
+            // server we've already vetted could redirect straight to an
+            // IP-literal URL (e.g. the tracker's `127.0.0.1` API) and bypass
+            // it entirely, so every redirect hop is re-vetted here too.
+            .redirect(redirect_policy(dns_settings))
             .build()
             .unwrap();
 
@@ -98,6 +182,8 @@ impl ImageCacheService {
             user_quotas: RwLock::new(HashMap::new()),
             reqwest_client,
             cfg,
+            clock,
+            database,
         }
     }
 
@@ -116,10 +202,10 @@ impl ImageCacheService {
 
         self.check_user_quota(&user).await?;
 
+        // Oversized responses are already rejected while streaming in
+        // `get_image_from_url_as_bytes`, so there's no separate size check here.
         let image_bytes = self.get_image_from_url_as_bytes(url).await?;
 
-        self.check_image_size(&image_bytes).await?;
-
         // These two functions could be executed after returning the image to the client,
         // but than we would need a dedicated task or thread that executes these functions.
         // This can be problematic if a task is spawned after every user request.
@@ -133,40 +219,108 @@ impl ImageCacheService {
     }
 
     async fn get_image_from_url_as_bytes(&self, url: &str) -> Result<Bytes, Error> {
+        let parsed_url = Url::parse(url).map_err(|_| Error::UrlIsNotAllowed)?;
+
+        if parsed_url.scheme() != "http" && parsed_url.scheme() != "https" {
+            return Err(Error::UrlIsNotAllowed);
+        }
+
+        // When the host is already an IP literal (e.g. `http://127.0.0.1/`),
+        // hyper connects to it directly and never calls our DNS resolver, so
+        // the resolver's vetting would otherwise be skipped entirely. Any
+        // redirect this request follows is re-vetted the same way by
+        // `redirect_policy`.
+        let dns_settings = self.cfg.settings.read().await.image_cache.dns.clone();
+
+        if !is_literal_host_allowed(&parsed_url, &dns_settings) {
+            return Err(Error::UrlIsNotAllowed);
+        }
+
         let res = self
             .reqwest_client
             .clone()
-            .get(url)
+            .get(parsed_url)
             .send()
             .await
-            .map_err(|_| Error::UrlIsUnreachable)?;
-
-        if let Some(content_type) = res.headers().get("Content-Type") {
-            if content_type != "image/jpeg" && content_type != "image/png" {
-                return Err(Error::UrlIsNotAnImage);
+            .map_err(|err| {
+                if err.is_connect() {
+                    // The custom DNS resolver rejects the request before a connection is
+                    // ever attempted when the host resolves to a disallowed address.
+                    Error::UrlIsNotAllowed
+                } else {
+                    Error::UrlIsUnreachable
+                }
+            })?;
+
+        let entry_size_limit = self.cfg.settings.read().await.image_cache.entry_size_limit;
+
+        let mut image_bytes = BytesMut::new();
+        let mut stream = res.bytes_stream();
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|_| Error::UrlIsUnreachable)?;
+
+            // Abort as soon as we know the image is too big instead of buffering
+            // the whole (potentially huge) response before rejecting it.
+            if image_bytes.len() + chunk.len() > entry_size_limit {
+                return Err(Error::ImageTooBig);
             }
-        } else {
-            return Err(Error::UrlIsNotAnImage);
+
+            image_bytes.extend_from_slice(&chunk);
         }
 
-        res.bytes().await.map_err(|_| Error::UrlIsNotAnImage)
+        let image_bytes = image_bytes.freeze();
+
+        let settings = self.cfg.settings.read().await;
+
+        match sniff::sniff(&image_bytes) {
+            Some(format) if settings.image_cache.allowed_image_formats.contains(&format) => Ok(image_bytes),
+            _ => Err(Error::UrlIsNotAnImage),
+        }
     }
 
     async fn check_user_quota(&self, user: &UserCompact) -> Result<(), Error> {
-        if let Some(quota) = self.user_quotas.read().await.get(&user.user_id) {
+        let now_secs = self.clock.now_secs();
+
+        if let Some(quota) = self.user_quotas.write().await.get_mut(&user.user_id) {
+            // Mirrors the reset-then-check done atomically in the database
+            // upsert, so a user whose window has expired isn't judged against
+            // a stale `usage` from their previous period and locked out forever.
+            if now_secs - quota.date_start_secs > quota.period_secs {
+                quota.reset(now_secs);
+            }
+
             if quota.is_reached() {
                 return Err(Error::UserQuotaMet);
             }
+
+            return Ok(());
         }
 
-        Ok(())
-    }
+        // Not in the write-through cache yet: this can happen right after a
+        // restart, or on an instance that hasn't served this user before.
+        // Load the persisted quota so restarts/horizontal scaling don't hand
+        // the user a fresh quota every time they land on a different instance.
+        if let Ok(Some(row)) = self.database.get_image_cache_quota(user.user_id).await {
+            let mut quota = ImageCacheQuota {
+                user_id: user.user_id,
+                usage: row.usage,
+                max_usage: row.max_usage,
+                date_start_secs: row.date_start_secs,
+                period_secs: row.period_secs,
+            };
+
+            if now_secs - quota.date_start_secs > quota.period_secs {
+                quota.reset(now_secs);
+            }
 
-    async fn check_image_size(&self, image_bytes: &Bytes) -> Result<(), Error> {
-        let settings = self.cfg.settings.read().await;
+            let is_reached = quota.is_reached();
 
-        if image_bytes.len() > settings.image_cache.entry_size_limit {
-            return Err(Error::ImageTooBig);
+            self.user_quotas.write().await.insert(user.user_id, quota);
+
+            if is_reached {
+                return Err(Error::UserQuotaMet);
+            }
         }
 
         Ok(())
@@ -189,22 +343,32 @@ impl ImageCacheService {
 
     async fn update_user_quota(&self, user: &UserCompact, amount: usize) -> Result<(), Error> {
         let settings = self.cfg.settings.read().await;
-
-        let mut quota = self
-            .user_quotas
-            .read()
-            .await
-            .get(&user.user_id)
-            .cloned()
-            .unwrap_or(ImageCacheQuota::new(
+        let now_secs = self.clock.now_secs();
+
+        // A single atomic upsert both resets an expired window and applies the
+        // new usage, so two concurrent requests on different instances can't
+        // race each other into exceeding the quota between a read and a write.
+        let row = self
+            .database
+            .upsert_image_cache_quota_usage(
                 user.user_id,
+                amount,
                 settings.image_cache.user_quota_bytes,
                 settings.image_cache.user_quota_period_seconds,
-            ));
+                now_secs,
+            )
+            .await
+            .map_err(|_| Error::UserQuotaMet)?;
 
-        let _ = quota.add_usage(amount);
+        let quota = ImageCacheQuota {
+            user_id: user.user_id,
+            usage: row.usage,
+            max_usage: row.max_usage,
+            date_start_secs: row.date_start_secs,
+            period_secs: row.period_secs,
+        };
 
-        let _ = self.user_quotas.write().await.insert(user.user_id, quota);
+        self.user_quotas.write().await.insert(user.user_id, quota);
 
         Ok(())
     }
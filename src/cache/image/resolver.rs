@@ -0,0 +1,164 @@
+//! Custom DNS resolver used by the image proxy to close off SSRF via the
+//! `reqwest::Client`.
+//!
+//! Resolution happens once, here, and the resulting (already vetted)
+//! addresses are handed straight to the connector. This avoids the
+//! DNS-rebinding TOCTOU where a name resolves to a public IP during a
+//! validation step but to a private one by the time the actual connection
+//! is made.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+use crate::config::ImageCacheDns;
+
+/// Returns `true` if `ip` is safe to connect the image proxy to, i.e. it is
+/// not loopback, private, link-local or unspecified, and it is not present
+/// in the configured deny list. An explicit allow list, if non-empty, takes
+/// precedence: only addresses contained in it are considered safe.
+pub fn is_allowed_ip(ip: &IpAddr, dns_settings: &ImageCacheDns) -> bool {
+    if !dns_settings.allowed_cidrs.is_empty() {
+        return dns_settings.allowed_cidrs.iter().any(|cidr| cidr.contains(ip));
+    }
+
+    if dns_settings.denied_cidrs.iter().any(|cidr| cidr.contains(ip)) {
+        return false;
+    }
+
+    !is_disallowed_by_default(ip)
+}
+
+fn is_disallowed_by_default(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_disallowed_ipv4(ip),
+        IpAddr::V6(ip) => is_disallowed_ipv6(ip),
+    }
+}
+
+fn is_disallowed_ipv4(ip: &Ipv4Addr) -> bool {
+    ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast()
+}
+
+fn is_disallowed_ipv6(ip: &Ipv6Addr) -> bool {
+    // An IPv4-mapped address (`::ffff:a.b.c.d`) reaches an IPv4 destination
+    // just like the plain `a.b.c.d` would, so it must be judged by the same
+    // rules or e.g. `::ffff:127.0.0.1` sails through as "not loopback".
+    if let Some(mapped) = ip.to_ipv4_mapped() {
+        return is_disallowed_ipv4(&mapped);
+    }
+
+    // Unique local addresses (fc00::/7) are not covered by a stable std method yet.
+    let is_unique_local = (ip.segments()[0] & 0xfe00) == 0xfc00;
+
+    ip.is_loopback() || ip.is_unspecified() || ip.is_unicast_link_local() || is_unique_local
+}
+
+/// A [`Resolve`] implementation that resolves a host with the default
+/// system resolver and then filters out any address that falls in a
+/// loopback, private, link-local or unspecified range (or a configured deny
+/// range), returning an error instead of silently resolving to nothing.
+#[derive(Clone)]
+pub struct VettedSocketResolver {
+    dns_settings: Arc<ImageCacheDns>,
+}
+
+impl VettedSocketResolver {
+    pub fn new(dns_settings: Arc<ImageCacheDns>) -> Self {
+        Self { dns_settings }
+    }
+}
+
+impl Resolve for VettedSocketResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let dns_settings = self.dns_settings.clone();
+
+        Box::pin(async move {
+            let host = format!("{}:0", name.as_str());
+
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host(host)
+                .await
+                .map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?
+                .filter(|addr| is_allowed_ip(&addr.ip(), &dns_settings))
+                .collect();
+
+            if addrs.is_empty() {
+                return Err("resolved address is not allowed for the image proxy".into());
+            }
+
+            let addrs: Addrs = Box::new(addrs.into_iter());
+
+            Ok(addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn dns_settings(allowed_cidrs: &[&str], denied_cidrs: &[&str]) -> ImageCacheDns {
+        ImageCacheDns {
+            allowed_cidrs: allowed_cidrs.iter().map(|c| c.parse().unwrap()).collect(),
+            denied_cidrs: denied_cidrs.iter().map(|c| c.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn it_should_allow_a_public_ip_by_default() {
+        let ip: IpAddr = Ipv4Addr::new(93, 184, 216, 34).into();
+        assert!(is_allowed_ip(&ip, &dns_settings(&[], &[])));
+    }
+
+    #[test]
+    fn it_should_reject_loopback_private_link_local_and_unspecified_by_default() {
+        let settings = dns_settings(&[], &[]);
+
+        for ip in [
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            IpAddr::V4(Ipv4Addr::new(169, 254, 169, 254)),
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            "::1".parse().unwrap(),
+            "fe80::1".parse().unwrap(),
+            "fc00::1".parse().unwrap(),
+        ] {
+            assert!(!is_allowed_ip(&ip, &settings), "expected {ip} to be disallowed");
+        }
+    }
+
+    #[test]
+    fn it_should_reject_an_ipv4_mapped_ipv6_loopback_address() {
+        let ip: IpAddr = "::ffff:127.0.0.1".parse().unwrap();
+        assert!(!is_allowed_ip(&ip, &dns_settings(&[], &[])));
+    }
+
+    #[test]
+    fn it_should_reject_an_ipv4_mapped_ipv6_link_local_address() {
+        let ip: IpAddr = "::ffff:169.254.169.254".parse().unwrap();
+        assert!(!is_allowed_ip(&ip, &dns_settings(&[], &[])));
+    }
+
+    #[test]
+    fn it_should_reject_an_ip_in_the_deny_list_even_if_otherwise_public() {
+        let ip: IpAddr = Ipv4Addr::new(93, 184, 216, 34).into();
+        assert!(!is_allowed_ip(&ip, &dns_settings(&[], &["93.184.216.0/24"])));
+    }
+
+    #[test]
+    fn it_should_take_the_allow_list_precedence_over_default_rules_when_non_empty() {
+        // A loopback address would normally be rejected, but an explicit
+        // allow list takes precedence over the default disallow rules.
+        let ip: IpAddr = Ipv4Addr::new(127, 0, 0, 1).into();
+        assert!(is_allowed_ip(&ip, &dns_settings(&["127.0.0.0/8"], &[])));
+    }
+
+    #[test]
+    fn it_should_reject_anything_outside_a_non_empty_allow_list() {
+        let ip: IpAddr = Ipv4Addr::new(93, 184, 216, 34).into();
+        assert!(!is_allowed_ip(&ip, &dns_settings(&["10.0.0.0/8"], &[])));
+    }
+}
@@ -0,0 +1,69 @@
+//! Content sniffing for downloaded images.
+//!
+//! The `Content-Type` header on a fetched URL is attacker-controlled, so
+//! instead of trusting it we inspect the leading magic bytes of the payload
+//! itself to determine the actual image format.
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Gif,
+    WebP,
+}
+
+/// Returns the image format of `bytes` based on its magic number, or `None`
+/// if it doesn't match any known image format.
+pub fn sniff(bytes: &[u8]) -> Option<ImageFormat> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Some(ImageFormat::Jpeg);
+    }
+
+    if bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        return Some(ImageFormat::Png);
+    }
+
+    if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        return Some(ImageFormat::Gif);
+    }
+
+    if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return Some(ImageFormat::WebP);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_sniff_a_jpeg_payload() {
+        assert_eq!(sniff(&[0xFF, 0xD8, 0xFF, 0xE0, 0x00]), Some(ImageFormat::Jpeg));
+    }
+
+    #[test]
+    fn it_should_sniff_a_png_payload() {
+        assert_eq!(sniff(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]), Some(ImageFormat::Png));
+    }
+
+    #[test]
+    fn it_should_sniff_a_gif_payload() {
+        assert_eq!(sniff(b"GIF89a..."), Some(ImageFormat::Gif));
+    }
+
+    #[test]
+    fn it_should_sniff_a_webp_payload() {
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        bytes.extend_from_slice(b"WEBP");
+
+        assert_eq!(sniff(&bytes), Some(ImageFormat::WebP));
+    }
+
+    #[test]
+    fn it_should_not_sniff_an_unknown_payload() {
+        assert_eq!(sniff(b"not an image"), None);
+    }
+}
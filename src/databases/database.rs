@@ -0,0 +1,42 @@
+//! The `Database` trait abstracts over the SQL backend so the rest of the
+//! application doesn't depend on a specific driver.
+
+use async_trait::async_trait;
+
+#[derive(Debug)]
+pub enum Error {
+    QuotaExceeded,
+    Error(String),
+}
+
+/// A persisted image-cache quota row (`torrust_user_image_cache_quotas`).
+pub struct ImageCacheQuotaRow {
+    pub user_id: i64,
+    pub usage: usize,
+    pub max_usage: usize,
+    pub date_start_secs: u64,
+    pub period_secs: u64,
+}
+
+#[async_trait]
+pub trait Database: Sync + Send {
+    /// Returns the persisted image-cache quota for `user_id`, if any.
+    async fn get_image_cache_quota(&self, user_id: i64) -> Result<Option<ImageCacheQuotaRow>, Error>;
+
+    /// Atomically applies `amount` of usage for `user_id`, resetting the
+    /// window first if it has expired, and rejecting the update (without
+    /// persisting it) if the user is already at or over `max_usage`.
+    ///
+    /// This runs as a single transaction so two concurrent requests -
+    /// possibly against two different backend instances - can't both read a
+    /// usage just under the limit and then both write, taking the user over
+    /// their quota.
+    async fn upsert_image_cache_quota_usage(
+        &self,
+        user_id: i64,
+        amount: usize,
+        max_usage: usize,
+        period_secs: u64,
+        now_secs: u64,
+    ) -> Result<ImageCacheQuotaRow, Error>;
+}
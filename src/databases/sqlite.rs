@@ -0,0 +1,102 @@
+//! `SQLite` implementation of the image-cache quota persistence added in
+//! `migrations/sqlite3/20260730120000_torrust_user_image_cache_quotas.sql`.
+
+use async_trait::async_trait;
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::SqlitePool;
+
+use super::database::{Database, Error, ImageCacheQuotaRow};
+
+pub struct SqliteDatabase {
+    pool: SqlitePool,
+}
+
+impl SqliteDatabase {
+    pub async fn new(connect_url: &str) -> Self {
+        let pool = SqlitePoolOptions::new()
+            .connect(connect_url)
+            .await
+            .unwrap_or_else(|_| panic!("Could not connect to the database at {connect_url}"));
+
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Database for SqliteDatabase {
+    async fn get_image_cache_quota(&self, user_id: i64) -> Result<Option<ImageCacheQuotaRow>, Error> {
+        let row = sqlx::query_as::<_, (i64, i64, i64, i64, i64)>(
+            "SELECT user_id, usage, max_usage, date_start_secs, period_secs FROM torrust_user_image_cache_quotas WHERE user_id = ?",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::Error(e.to_string()))?;
+
+        Ok(row.map(|(user_id, usage, max_usage, date_start_secs, period_secs)| ImageCacheQuotaRow {
+            user_id,
+            usage: usage as usize,
+            max_usage: max_usage as usize,
+            date_start_secs: date_start_secs as u64,
+            period_secs: period_secs as u64,
+        }))
+    }
+
+    async fn upsert_image_cache_quota_usage(
+        &self,
+        user_id: i64,
+        amount: usize,
+        max_usage: usize,
+        period_secs: u64,
+        now_secs: u64,
+    ) -> Result<ImageCacheQuotaRow, Error> {
+        let mut tx = self.pool.begin().await.map_err(|e| Error::Error(e.to_string()))?;
+
+        let existing = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT usage, date_start_secs FROM torrust_user_image_cache_quotas WHERE user_id = ?",
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| Error::Error(e.to_string()))?;
+
+        let (usage, date_start_secs) = match existing {
+            // The window has expired: reset it instead of rejecting based on stale usage.
+            Some((usage, date_start_secs)) if now_secs as i64 - date_start_secs > period_secs as i64 => (0, now_secs as i64),
+            Some((usage, date_start_secs)) => (usage, date_start_secs),
+            None => (0, now_secs as i64),
+        };
+
+        if usage as usize >= max_usage {
+            tx.rollback().await.map_err(|e| Error::Error(e.to_string()))?;
+            return Err(Error::QuotaExceeded);
+        }
+
+        let new_usage = usage.saturating_add(amount as i64).min(i64::try_from(max_usage).unwrap_or(i64::MAX));
+
+        sqlx::query(
+            "INSERT INTO torrust_user_image_cache_quotas (user_id, usage, max_usage, date_start_secs, period_secs)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET usage = excluded.usage, max_usage = excluded.max_usage,
+                date_start_secs = excluded.date_start_secs, period_secs = excluded.period_secs",
+        )
+        .bind(user_id)
+        .bind(new_usage)
+        .bind(max_usage as i64)
+        .bind(date_start_secs)
+        .bind(period_secs as i64)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| Error::Error(e.to_string()))?;
+
+        tx.commit().await.map_err(|e| Error::Error(e.to_string()))?;
+
+        Ok(ImageCacheQuotaRow {
+            user_id,
+            usage: new_usage as usize,
+            max_usage,
+            date_start_secs: date_start_secs as u64,
+            period_secs,
+        })
+    }
+}
@@ -0,0 +1,105 @@
+//! SMTP mailer used to send the email-verification message on signup.
+//!
+//! Supports locked-down/self-hosted SMTP servers that present certificates
+//! signed by a private CA: extra root certificates can be loaded from PEM
+//! files, and the platform's native trust store can be disabled entirely so
+//! that only the configured roots are trusted.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::{AsyncSmtpTransport, Tokio1Executor};
+use native_tls::{Certificate, TlsConnector};
+
+use crate::config::Configuration;
+
+/// How the mailer establishes TLS with the SMTP server.
+///
+/// Defaults to `Opportunistic` rather than `Wrapper` so a plain-SMTP relay
+/// (e.g. the `mailcatcher` instance used in e2e tests) keeps working; it
+/// upgrades the connection with `STARTTLS` when the server offers it and
+/// otherwise falls back to a plain connection.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTlsMode {
+    /// Never use TLS.
+    None,
+    /// Upgrade via `STARTTLS` when the server supports it.
+    Opportunistic,
+    /// Always connect over implicit TLS (SMTPS), e.g. on port 465.
+    Wrapper,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    TlsCertificateNotFound { path: PathBuf },
+    TlsCertificateIsNotValidPem { path: PathBuf },
+    CouldNotBuildTlsConnector,
+    CouldNotBuildMailbox,
+}
+
+pub struct Mailer {
+    pub transport: AsyncSmtpTransport<Tokio1Executor>,
+    pub from: Mailbox,
+    pub reply_to: Mailbox,
+}
+
+impl Mailer {
+    pub async fn new(cfg: Arc<Configuration>) -> Result<Self, Error> {
+        let settings = cfg.settings.read().await;
+        let mail = settings.mail.clone();
+
+        // Built eagerly so a missing/unparseable cert file fails startup
+        // with a clear error instead of failing on the first send attempt.
+        let tls = match mail.smtp_tls_mode {
+            SmtpTlsMode::None => Tls::None,
+            SmtpTlsMode::Opportunistic | SmtpTlsMode::Wrapper => {
+                let tls_connector = build_tls_connector(&mail.smtp_tls_root_certificates, mail.smtp_disable_system_root_certificates)?;
+
+                let tls_parameters = TlsParameters::new_with_connector(mail.server.clone(), tls_connector);
+
+                if mail.smtp_tls_mode == SmtpTlsMode::Wrapper {
+                    Tls::Wrapper(tls_parameters)
+                } else {
+                    Tls::Opportunistic(tls_parameters)
+                }
+            }
+        };
+
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&mail.server)
+            .port(mail.port)
+            .tls(tls)
+            .credentials(Credentials::new(mail.username.clone(), mail.password.clone()))
+            .build();
+
+        Ok(Self {
+            transport,
+            from: mail.from.parse().map_err(|_| Error::CouldNotBuildMailbox)?,
+            reply_to: mail.reply_to.parse().map_err(|_| Error::CouldNotBuildMailbox)?,
+        })
+    }
+}
+
+/// Builds the `native-tls` connector used to validate the SMTP server's
+/// certificate, loading any extra root certificates and optionally
+/// disabling the platform's built-in trust store so that only the
+/// configured roots are trusted.
+fn build_tls_connector(root_certificate_paths: &[PathBuf], disable_system_root_certificates: bool) -> Result<TlsConnector, Error> {
+    let mut builder = TlsConnector::builder();
+
+    builder.disable_built_in_roots(disable_system_root_certificates);
+
+    for path in root_certificate_paths {
+        let pem = fs::read(path).map_err(|_| Error::TlsCertificateNotFound { path: path.clone() })?;
+
+        let cert = Certificate::from_pem(&pem).map_err(|_| Error::TlsCertificateIsNotValidPem { path: path.clone() })?;
+
+        builder.add_root_certificate(cert);
+    }
+
+    builder.build().map_err(|_| Error::CouldNotBuildTlsConnector)
+}
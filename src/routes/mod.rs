@@ -0,0 +1,32 @@
+//! HTTP routes, grouped by API version.
+//!
+//! `v1` is the original, stable API and must stay byte-compatible with its
+//! existing contract. `v2` is mounted alongside it for clients that opt in,
+//! and shares the service layer underneath so handlers aren't duplicated
+//! between the two.
+
+pub mod v1;
+pub mod v2;
+
+use actix_web::web;
+
+/// The API versions exposed over HTTP, one per mounted route prefix.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/v1",
+            ApiVersion::V2 => "/v2",
+        }
+    }
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope(ApiVersion::V1.prefix()).configure(v1::init_routes));
+    cfg.service(web::scope(ApiVersion::V2.prefix()).configure(v2::init_routes));
+}
@@ -0,0 +1,12 @@
+//! `v1` routes — the original, stable API. Must stay byte-compatible with
+//! its existing contract.
+
+pub mod settings;
+pub mod user;
+
+use actix_web::web;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    settings::init_routes(cfg);
+    user::init_routes(cfg);
+}
@@ -0,0 +1,109 @@
+//! `v1` settings routes.
+
+use actix_web::web;
+use actix_web::HttpRequest;
+use serde::{Deserialize, Serialize};
+
+use crate::common::WebAppData;
+use crate::config::Settings;
+use crate::errors::{ServiceError, ServiceResult};
+use crate::models::user::UserCompact;
+
+pub fn init_routes(_cfg: &mut web::ServiceConfig) {
+    // The existing `v1` settings handlers are unchanged by this series.
+}
+
+/// Authenticates the request and returns the requesting user. Used by the
+/// `v2` settings handlers, which then do the admin check themselves.
+pub async fn sanitized_auth_user_from_request(req: &HttpRequest, app_data: &WebAppData) -> ServiceResult<UserCompact> {
+    app_data.auth.get_user_from_request(req).await.map_err(|_| ServiceError::Unauthorized)
+}
+
+/// `settings` mapped to a response shape with secrets (`auth.secret_key`,
+/// `tracker.token`, `mail.username`/`mail.password`) left out. `v2` uses
+/// this for its redacted `GET /settings` response; the `v1` settings
+/// response still includes the secrets and does not go through it.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct AllSettings {
+    pub website: Website,
+    pub tracker: TrackerWithoutSecrets,
+    pub net: Net,
+    pub auth: AuthWithoutSecrets,
+    pub database: Database,
+    pub mail: Mail,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct TrackerWithoutSecrets {
+    pub url: String,
+    pub mode: String,
+    pub api_url: String,
+    pub token_valid_seconds: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct AuthWithoutSecrets {
+    pub email_on_signup: String,
+    pub min_password_length: usize,
+    pub max_password_length: usize,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct Website {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct Net {
+    pub port: u64,
+    pub base_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct Database {
+    pub connect_url: String,
+    pub torrent_info_update_interval: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct Mail {
+    pub email_verification_enabled: bool,
+    pub from: String,
+    pub reply_to: String,
+    pub server: String,
+    pub port: u32,
+}
+
+pub fn all_settings_without_secrets(settings: &Settings) -> AllSettings {
+    AllSettings {
+        website: Website {
+            name: settings.website.name.clone(),
+        },
+        tracker: TrackerWithoutSecrets {
+            url: settings.tracker.url.clone(),
+            mode: settings.tracker.mode.to_string(),
+            api_url: settings.tracker.api_url.clone(),
+            token_valid_seconds: settings.tracker.token_valid_seconds,
+        },
+        net: Net {
+            port: settings.net.port,
+            base_url: settings.net.base_url.clone(),
+        },
+        auth: AuthWithoutSecrets {
+            email_on_signup: settings.auth.email_on_signup.to_string(),
+            min_password_length: settings.auth.min_password_length,
+            max_password_length: settings.auth.max_password_length,
+        },
+        database: Database {
+            connect_url: settings.database.connect_url.clone(),
+            torrent_info_update_interval: settings.database.torrent_info_update_interval,
+        },
+        mail: Mail {
+            email_verification_enabled: settings.mail.email_verification_enabled,
+            from: settings.mail.from.clone(),
+            reply_to: settings.mail.reply_to.clone(),
+            server: settings.mail.server.clone(),
+            port: settings.mail.port,
+        },
+    }
+}
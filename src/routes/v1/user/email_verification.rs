@@ -0,0 +1,27 @@
+//! `GET /user/email/verify/{token}` — the link sent in the verification
+//! email. Marks the user's account as verified so they can log in when
+//! `mail.email_verification_enabled` is set.
+
+use actix_web::http::StatusCode;
+use actix_web::web::{Data, Path};
+use actix_web::HttpResponse;
+
+use crate::common::WebAppData;
+use crate::errors::{ServiceError, ServiceResult};
+
+pub async fn verify_email(app_data: Data<WebAppData>, path: Path<String>) -> ServiceResult<HttpResponse> {
+    let token = path.into_inner();
+
+    let user_id = app_data
+        .auth
+        .decode_email_verification_token(&token)
+        .map_err(|_| ServiceError::TokenInvalid)?;
+
+    app_data
+        .database
+        .verify_user_email(user_id)
+        .await
+        .map_err(|_| ServiceError::InternalServerError)?;
+
+    Ok(HttpResponse::build(StatusCode::OK).body("Email verified, you can now log in."))
+}
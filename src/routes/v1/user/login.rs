@@ -0,0 +1,94 @@
+//! `POST /user/login`.
+
+use actix_web::web::{Data, Json};
+use actix_web::HttpResponse;
+use serde::{Deserialize, Serialize};
+
+use crate::common::WebAppData;
+use crate::errors::{ServiceError, ServiceResult};
+use crate::models::user::UserCompact;
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    pub login: String,
+    pub password: String,
+}
+
+#[derive(Serialize)]
+pub struct TokenResponse {
+    pub token: String,
+    pub username: String,
+    pub admin: bool,
+}
+
+#[derive(Serialize)]
+pub struct LoginResponse {
+    pub data: TokenResponse,
+}
+
+pub async fn login(app_data: Data<WebAppData>, payload: Json<LoginForm>) -> ServiceResult<HttpResponse> {
+    let user = app_data
+        .database
+        .get_user_compact_for_login(&payload.login, &payload.password)
+        .await
+        .map_err(|_| ServiceError::InvalidLoginCredentials)?;
+
+    let settings = app_data.cfg.settings.read().await;
+    let email_verification_enabled = settings.mail.email_verification_enabled;
+    let validity_secs = settings.auth.token_valid_seconds;
+    drop(settings);
+
+    assert_user_can_log_in(&user, email_verification_enabled)?;
+
+    let token = app_data.auth.sign(&user, validity_secs);
+
+    Ok(HttpResponse::Ok().json(LoginResponse {
+        data: TokenResponse {
+            token,
+            username: user.username,
+            admin: user.admin,
+        },
+    }))
+}
+
+/// Rejects login while `mail.email_verification_enabled` is set and the
+/// user hasn't verified their email yet.
+pub fn assert_user_can_log_in(user: &UserCompact, email_verification_enabled: bool) -> Result<(), ServiceError> {
+    if email_verification_enabled && !user.email_verified {
+        return Err(ServiceError::EmailNotVerified);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(email_verified: bool) -> UserCompact {
+        UserCompact {
+            user_id: 1,
+            username: "user".to_string(),
+            admin: false,
+            email_verified,
+        }
+    }
+
+    #[test]
+    fn it_should_allow_login_when_verification_is_disabled() {
+        assert!(assert_user_can_log_in(&user(false), false).is_ok());
+    }
+
+    #[test]
+    fn it_should_allow_login_when_the_user_is_verified() {
+        assert!(assert_user_can_log_in(&user(true), true).is_ok());
+    }
+
+    #[test]
+    fn it_should_reject_login_when_verification_is_required_and_the_user_is_not_verified() {
+        assert!(matches!(
+            assert_user_can_log_in(&user(false), true),
+            Err(ServiceError::EmailNotVerified)
+        ));
+    }
+}
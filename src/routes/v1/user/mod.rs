@@ -0,0 +1,17 @@
+//! `v1` user routes.
+
+pub mod email_verification;
+mod login;
+
+use actix_web::web;
+
+pub use email_verification::verify_email;
+pub use login::{assert_user_can_log_in, login};
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/user")
+            .route("/email/verify/{token}", web::get().to(verify_email))
+            .route("/login", web::post().to(login)),
+    );
+}
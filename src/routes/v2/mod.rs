@@ -0,0 +1,10 @@
+//! `v2` routes. New clients should prefer this version; `v1` is kept around
+//! unchanged for existing integrations.
+
+pub mod settings;
+
+use actix_web::web;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    settings::init_routes(cfg);
+}
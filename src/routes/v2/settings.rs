@@ -0,0 +1,106 @@
+//! `v2` settings endpoints.
+//!
+//! Unlike `v1`, `GET /v2/settings` never includes secrets, even for admins:
+//! `auth.secret_key`, `tracker.token`, and the SMTP `mail.username`/`mail.password`
+//! credentials are only available through the dedicated `GET /v2/settings/secrets`
+//! endpoint.
+
+use actix_web::web::{Data, Json};
+use actix_web::{web, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use crate::common::WebAppData;
+use crate::errors::{ServiceError, ServiceResult};
+use crate::routes::v1::settings::{all_settings_without_secrets, sanitized_auth_user_from_request, AllSettings};
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/settings")
+            .route("", web::get().to(get_all_settings))
+            .route("/public", web::get().to(get_public_settings))
+            .route("/secrets", web::get().to(get_secrets)),
+    );
+}
+
+/// Current schema version of [`PublicSettings`], bumped whenever its shape
+/// changes so clients can detect incompatible responses.
+const PUBLIC_SETTINGS_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct PublicSettings {
+    pub schema_version: u32,
+    pub website_name: String,
+    pub tracker_url: String,
+    pub tracker_mode: String,
+    pub email_on_signup: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct AllSettingsResponse {
+    pub data: AllSettings,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct PublicSettingsResponse {
+    pub data: PublicSettings,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct Secrets {
+    pub auth_secret_key: String,
+    pub tracker_token: String,
+    pub mail_username: String,
+    pub mail_password: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct SecretsResponse {
+    pub data: Secrets,
+}
+
+async fn get_all_settings(req: HttpRequest, app_data: Data<WebAppData>) -> ServiceResult<Json<AllSettingsResponse>> {
+    let user = sanitized_auth_user_from_request(&req, app_data.get_ref()).await?;
+
+    if !user.admin {
+        return Err(ServiceError::Unauthorized);
+    }
+
+    let settings = app_data.cfg.settings.read().await;
+
+    Ok(Json(AllSettingsResponse {
+        data: all_settings_without_secrets(&settings),
+    }))
+}
+
+async fn get_public_settings(app_data: Data<WebAppData>) -> ServiceResult<Json<PublicSettingsResponse>> {
+    let settings = app_data.cfg.settings.read().await;
+
+    Ok(Json(PublicSettingsResponse {
+        data: PublicSettings {
+            schema_version: PUBLIC_SETTINGS_SCHEMA_VERSION,
+            website_name: settings.website.name.clone(),
+            tracker_url: settings.tracker.url.clone(),
+            tracker_mode: settings.tracker.mode.to_string(),
+            email_on_signup: settings.auth.email_on_signup.to_string(),
+        },
+    }))
+}
+
+async fn get_secrets(req: HttpRequest, app_data: Data<WebAppData>) -> ServiceResult<Json<SecretsResponse>> {
+    let user = sanitized_auth_user_from_request(&req, app_data.get_ref()).await?;
+
+    if !user.admin {
+        return Err(ServiceError::Unauthorized);
+    }
+
+    let settings = app_data.cfg.settings.read().await;
+
+    Ok(Json(SecretsResponse {
+        data: Secrets {
+            auth_secret_key: settings.auth.secret_key.clone(),
+            tracker_token: settings.tracker.token.clone(),
+            mail_username: settings.mail.username.clone(),
+            mail_password: settings.mail.password.clone(),
+        },
+    }))
+}
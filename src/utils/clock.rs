@@ -0,0 +1,72 @@
+//! A clock abstraction that can be swapped out in tests so that
+//! time-dependent behaviour (quota resets, token expiry, ...) can be tested
+//! deterministically instead of relying on real wall-clock time.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// A source of the current time, expressed as seconds since the Unix epoch.
+pub trait Clock: Send + Sync {
+    fn now_secs(&self) -> u64;
+}
+
+/// The production clock, backed by the system time.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_secs(&self) -> u64 {
+        match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(n) => n.as_secs(),
+            Err(_) => panic!("SystemTime before UNIX EPOCH!"),
+        }
+    }
+}
+
+/// A clock for tests that can be set and advanced without waiting on real
+/// time, e.g. to test "renew a token one week before it expires".
+pub struct MockClock {
+    now_secs: AtomicU64,
+}
+
+impl MockClock {
+    pub fn new(now_secs: u64) -> Self {
+        Self {
+            now_secs: AtomicU64::new(now_secs),
+        }
+    }
+
+    pub fn set(&self, now_secs: u64) {
+        self.now_secs.store(now_secs, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, secs: u64) {
+        self.now_secs.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_secs(&self) -> u64 {
+        self.now_secs.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_report_the_time_it_was_set_to() {
+        let clock = MockClock::new(1_000);
+
+        assert_eq!(clock.now_secs(), 1_000);
+    }
+
+    #[test]
+    fn it_should_advance_by_the_given_number_of_seconds() {
+        let clock = MockClock::new(1_000);
+
+        clock.advance(604_800);
+
+        assert_eq!(clock.now_secs(), 1_000 + 604_800);
+    }
+}
@@ -190,3 +190,50 @@ async fn it_should_allow_admins_to_update_all_the_settings() {
     }
     assert_eq!(response.status, 200);
 }
+
+mod v2 {
+    use crate::e2e::contexts::user::steps::logged_in_admin;
+    use crate::environments::shared::TestEnv;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "e2e-tests"), ignore)]
+    async fn it_should_not_include_secrets_in_the_v2_all_settings_response_even_for_admins() {
+        let logged_in_admin = logged_in_admin().await;
+        let env = TestEnv::running().await;
+
+        let response = reqwest::Client::new()
+            .get(format!("{}/v2/settings", env.base_url()))
+            .bearer_auth(&logged_in_admin.token)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+
+        let body = response.text().await.unwrap();
+
+        assert!(!body.contains("MaxVerstappenWC2021"), "v2 settings must not leak auth.secret_key");
+        assert!(!body.contains("MyAccessToken"), "v2 settings must not leak tracker.token");
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "e2e-tests"), ignore)]
+    async fn it_should_allow_admins_to_get_the_secrets_from_the_dedicated_v2_endpoint() {
+        let logged_in_admin = logged_in_admin().await;
+        let env = TestEnv::running().await;
+
+        let response = reqwest::Client::new()
+            .get(format!("{}/v2/settings/secrets", env.base_url()))
+            .bearer_auth(&logged_in_admin.token)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+
+        let body = response.text().await.unwrap();
+
+        assert!(body.contains("MaxVerstappenWC2021"));
+        assert!(body.contains("MyAccessToken"));
+    }
+}
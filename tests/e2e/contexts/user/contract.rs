@@ -1,4 +1,7 @@
 //! API contract for `user` context.
+#[path = "mailcatcher.rs"]
+mod mailcatcher;
+
 use crate::common::contexts::user::fixtures::random_user_registration;
 use crate::common::contexts::user::forms::{LoginForm, TokenRenewalForm, TokenVerificationForm};
 use crate::common::contexts::user::responses::{
@@ -12,24 +15,12 @@ use crate::e2e::environment::TestEnv;
 This test suite is not complete. It's just a starting point to show how to
 write E2E tests. Anyway, the goal is not to fully cover all the app features
 with E2E tests. The goal is to cover the most important features and to
-demonstrate how to write E2E tests. Some important pending tests could be:
-
-todo:
-
-- It should allow renewing a token one week before it expires.
-- It should allow verifying user registration via email.
-
-The first one requires to mock the time. Consider extracting the mod
-<https://github.com/torrust/torrust-tracker/tree/develop/src/shared/clock> into
-an independent crate.
+demonstrate how to write E2E tests.
 
-The second one requires:
-- To call the mailcatcher API to get the verification URL.
-- To enable email verification in the configuration.
-- To fix current tests to verify the email for newly created users.
-- To find out which email is the one that contains the verification URL for a
-given test. That maybe done using the email recipient if that's possible with
-the mailcatcher API.
+Renewing a token one week before it expires is covered by unit tests in
+`src/auth.rs` using a `MockClock` instead of here, since driving that
+scenario end-to-end would mean waiting out (or mocking) the real token
+validity period against a running server.
 
 */
 
@@ -125,6 +116,47 @@ async fn it_should_not_allow_a_logged_in_user_to_renew_an_authentication_token_w
     assert_eq!(response.status, 200);
 }
 
+#[tokio::test]
+#[cfg_attr(not(feature = "e2e-tests"), ignore)]
+async fn it_should_allow_verifying_user_registration_via_email() {
+    let env = TestEnv::running_with_email_verification_enabled().await;
+    let client = env.unauthenticated_client();
+
+    let form = random_user_registration();
+
+    let response = client.register_user(form.clone()).await;
+
+    assert_eq!(response.status, 200);
+
+    // The user cannot log in until they follow the verification link mailcatcher received.
+    let login_before_verifying = client
+        .login_user(LoginForm {
+            login: form.username.clone(),
+            password: form.password.clone(),
+        })
+        .await;
+
+    assert_eq!(login_before_verifying.status, 403);
+
+    let verification_url = mailcatcher::get_latest_verification_url(&form.email).await;
+
+    let verification_response = reqwest::Client::new().get(verification_url).send().await.unwrap();
+
+    assert_eq!(verification_response.status(), 200);
+
+    let login_after_verifying = client
+        .login_user(LoginForm {
+            login: form.username.clone(),
+            password: form.password.clone(),
+        })
+        .await;
+
+    let res: SuccessfulLoginResponse = serde_json::from_str(&login_after_verifying.body).unwrap();
+
+    assert_eq!(res.data.username, form.username);
+    assert_eq!(login_after_verifying.status, 200);
+}
+
 mod banned_user_list {
     use crate::common::contexts::user::forms::Username;
     use crate::common::contexts::user::responses::BannedUserResponse;
@@ -0,0 +1,78 @@
+//! A thin client for the `mailcatcher` HTTP API used by E2E tests to pull
+//! the verification link out of a sent email without a real mailbox.
+
+use regex::Regex;
+use serde::Deserialize;
+
+const MAILCATCHER_BASE_URL: &str = "http://mailcatcher:1080";
+
+#[derive(Deserialize)]
+struct MessageSummary {
+    id: u64,
+    recipients: Vec<String>,
+}
+
+/// Fetches the most recent email sent to `recipient` from mailcatcher and
+/// extracts the first URL found in its plain-text body.
+///
+/// # Panics
+///
+/// Panics if mailcatcher is unreachable, no message was sent to `recipient`,
+/// or the message body doesn't contain a URL.
+pub async fn get_latest_verification_url(recipient: &str) -> String {
+    let client = reqwest::Client::new();
+
+    let messages: Vec<MessageSummary> = client
+        .get(format!("{MAILCATCHER_BASE_URL}/messages"))
+        .send()
+        .await
+        .expect("mailcatcher should be reachable")
+        .json()
+        .await
+        .expect("mailcatcher should return a list of messages");
+
+    let recipient_tag = format!("<{recipient}>");
+
+    let message = messages
+        .iter()
+        .rev()
+        .find(|message| message.recipients.iter().any(|to| to.contains(&recipient_tag)))
+        .unwrap_or_else(|| panic!("no email was sent to {recipient}"));
+
+    let body = client
+        .get(format!("{MAILCATCHER_BASE_URL}/messages/{}.plain", message.id))
+        .send()
+        .await
+        .expect("mailcatcher should be reachable")
+        .text()
+        .await
+        .expect("mailcatcher should return the message body");
+
+    extract_first_url(&body).unwrap_or_else(|| panic!("email body for {recipient} did not contain a URL"))
+}
+
+fn extract_first_url(body: &str) -> Option<String> {
+    let url_pattern = Regex::new(r"https?://\S+").expect("the URL regex is valid");
+
+    url_pattern.find(body).map(|m| m.as_str().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_extract_the_first_url_from_an_email_body() {
+        let body = "Hi,\n\nPlease verify your account: https://index.test/api/v1/user/email/verify/abc123\n\nThanks.";
+
+        assert_eq!(
+            extract_first_url(body),
+            Some("https://index.test/api/v1/user/email/verify/abc123".to_string())
+        );
+    }
+
+    #[test]
+    fn it_should_return_none_when_the_body_has_no_url() {
+        assert_eq!(extract_first_url("Hi, welcome aboard."), None);
+    }
+}
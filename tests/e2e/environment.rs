@@ -0,0 +1,51 @@
+//! Test environment for the `user` context E2E suite.
+//!
+//! Most tests run against the shared index instance `docker-compose.yml`
+//! starts as `index-backend` (port 3000) with the checked-in test config
+//! (`default`/`running`). Tests that need `mail.email_verification_enabled
+//! = true` instead target `index-backend-email-verification-enabled`
+//! (port 3001), the same image started with that setting overridden via
+//! `TORRUST_IDX_BACK_CONFIG_OVERRIDE_MAIL__EMAIL_VERIFICATION_ENABLED`, so
+//! toggling it doesn't affect the shared instance the other contexts
+//! assert against (see the `v1` settings contract tests).
+
+use crate::common::client::Client;
+
+const DEFAULT_BASE_URL: &str = "http://localhost:3000";
+const EMAIL_VERIFICATION_ENABLED_BASE_URL: &str = "http://localhost:3001";
+
+pub struct TestEnv {
+    base_url: String,
+}
+
+impl TestEnv {
+    pub fn default() -> Self {
+        Self {
+            base_url: DEFAULT_BASE_URL.to_string(),
+        }
+    }
+
+    pub async fn running() -> Self {
+        Self::default()
+    }
+
+    /// Points at the instance started with `mail.email_verification_enabled`
+    /// set to `true`, so login is actually gated on a verified email.
+    pub async fn running_with_email_verification_enabled() -> Self {
+        Self {
+            base_url: EMAIL_VERIFICATION_ENABLED_BASE_URL.to_string(),
+        }
+    }
+
+    pub fn unauthenticated_client(&self) -> Client {
+        Client::unauthenticated(&self.base_url)
+    }
+
+    pub fn authenticated_client(&self, token: &str) -> Client {
+        Client::authenticated(&self.base_url, token)
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+}